@@ -1,7 +1,7 @@
 //! Simple rules engine that represents requirements as a tree, with each node having one or more requirements in order to be "Met".
 //!
 //! A tree of rules is constructed, and then the [`.check()`][1] method is called.
-//! `map` is a `field: value` mapping of facts that will be given to each node in the tree for testing.
+//! `map` is a `field: value` mapping of typed [`Value`][2] facts that will be given to each node in the tree for testing.
 //!
 //! Status output can be either `Met`, `NotMet`, or `Unknown` if the tested field is not present in the map.
 //!
@@ -22,7 +22,7 @@
 //! ]);
 //! let mut facts = BTreeMap::new();
 //! facts.insert("name".into(), "John Doe".into());
-//! facts.insert("fav_number".into(), "5".into());
+//! facts.insert("fav_number".into(), 5.into());
 //! let result = tree.check(&facts);
 //! println!("{:?}", result);
 //! assert!(result.status == ruuls::Status::Met);
@@ -54,6 +54,7 @@
 //! ```
 //!
 //! [1]: enum.Rule.html#method.check
+//! [2]: enum.Value.html
 
 
 #![feature(structural_match, rustc_attrs, proc_macro)]
@@ -63,8 +64,11 @@ extern crate serde;
 #[macro_use] extern crate serde_derive;
 
 mod ruuls;
+mod parser;
 
-pub use ruuls::{Constraint, Rule, RuleResult, Status};
+pub use ruuls::{Comparator, Constraint, OrderingOp, Rule, RuleResult, Status, UnsatisfiableError,
+                 Value};
+pub use parser::ParseError;
 
 /// Creates a `Rule` where all child `Rule`s must be `Met`
 /// 
@@ -100,40 +104,93 @@ pub fn string_equals(description: &str, field: &str, val: &str) -> Rule {
                Constraint::StringEquals(val.into()))
 }
 
-/// Creates a rule for int comparison.  
+/// Creates a rule for int comparison.
 ///
-///If the checked value is not convertible to an integer, the result is `NotMet`
+/// If the checked `Value` is not an `Int` (or a whole-numbered `Float`), the result is `NotMet`
 pub fn int_equals(description: &str, field: &str, val: i32) -> Rule {
     Rule::Rule(description.into(), field.into(), Constraint::IntEquals(val))
 }
 
-/// Creates a rule for int range comparison with the interval `[start, end]`.  
+/// Creates a rule for int range comparison with the interval `[start, end]`.
 ///
-/// If the checked value is not convertible to an integer, the result is `NotMet`
+/// If the checked `Value` is not an `Int` (or a whole-numbered `Float`), the result is `NotMet`
 pub fn int_range(description: &str, field: &str, start: i32, end: i32) -> Rule {
     Rule::Rule(description.into(),
                field.into(),
                Constraint::IntRange(start, end))
 }
 
-/// Creates a rule for boolean comparison.  
+/// Creates a rule for boolean comparison.
 ///
-/// Only input values of `"true"` (case-insensitive) are considered `true`, all others are considered `false`
+/// If the checked `Value` is not a `Bool`, the result is `NotMet`
 pub fn boolean(description: &str, field: &str, val: bool) -> Rule {
     Rule::Rule(description.into(), field.into(), Constraint::Boolean(val))
 }
 
+/// Creates a rule for `field < val`.
+///
+/// If the checked `Value` is not an `Int` (or a whole-numbered `Float`), the result is `NotMet`
+pub fn int_less_than(description: &str, field: &str, val: i32) -> Rule {
+    Rule::Rule(description.into(), field.into(), Constraint::IntLessThan(val))
+}
+
+/// Creates a rule for `field > val`.
+///
+/// If the checked `Value` is not an `Int` (or a whole-numbered `Float`), the result is `NotMet`
+pub fn int_greater_than(description: &str, field: &str, val: i32) -> Rule {
+    Rule::Rule(description.into(), field.into(), Constraint::IntGreaterThan(val))
+}
+
+/// Creates a rule for `field <= val`.
+///
+/// If the checked `Value` is not an `Int` (or a whole-numbered `Float`), the result is `NotMet`
+pub fn int_less_or_equal(description: &str, field: &str, val: i32) -> Rule {
+    Rule::Rule(description.into(),
+               field.into(),
+               Constraint::IntLessOrEqual(val))
+}
+
+/// Creates a rule for `field >= val`.
+///
+/// If the checked `Value` is not an `Int` (or a whole-numbered `Float`), the result is `NotMet`
+pub fn int_greater_or_equal(description: &str, field: &str, val: i32) -> Rule {
+    Rule::Rule(description.into(),
+               field.into(),
+               Constraint::IntGreaterOrEqual(val))
+}
+
+/// Creates a rule for ordered string comparison against `bound`, using `comparator` to
+/// decide the ordering rather than relying on `Ord`.
+///
+/// If the checked `Value` is not a `Str`, the result is `NotMet`
+pub fn string_ordered(description: &str,
+                       field: &str,
+                       op: OrderingOp,
+                       bound: &str,
+                       comparator: Comparator)
+                       -> Rule {
+    Rule::Rule(description.into(),
+               field.into(),
+               Constraint::StringOrdered {
+                   op: op,
+                   bound: bound.into(),
+                   comparator: comparator,
+               })
+}
+
 
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
-    use super::{and, or, n_of, string_equals, int_equals, int_range, boolean, Status};
+    use super::{and, or, n_of, string_equals, int_equals, int_range, int_less_than,
+                int_greater_than, int_less_or_equal, int_greater_or_equal, string_ordered,
+                boolean, Comparator, OrderingOp, Status, UnsatisfiableError, Value};
 
-    fn get_test_data() -> BTreeMap<String, String> {
+    fn get_test_data() -> BTreeMap<String, Value> {
         let mut map = BTreeMap::new();
-        map.insert("foo".into(), "1".into());
+        map.insert("foo".into(), 1.into());
         map.insert("bar".into(), "bar".into());
-        map.insert("baz".into(), "true".into());
+        map.insert("baz".into(), true.into());
         map
     }
 
@@ -247,6 +304,22 @@ mod tests {
 
     }
 
+    #[test]
+    fn n_of_count_greater_than_children() {
+        let map = get_test_data();
+        // A count greater than the number of children can never be Met, but shouldn't panic
+        let mut root = n_of(100,
+                            vec![int_equals("foo = 1", "foo", 1),
+                                 string_equals("bar = 'bar'", "bar", "bar")]);
+        let mut res = root.check(&map);
+        assert!(res.status != Status::Met);
+
+        root = n_of(100,
+                    vec![int_equals("foo = 2", "foo", 2), string_equals("bar = 'baz'", "bar", "baz")]);
+        res = root.check(&map);
+        assert!(res.status == Status::NotMet);
+    }
+
     #[test]
     fn string_equals_rule() {
         let map = get_test_data();
@@ -308,14 +381,151 @@ mod tests {
         res = rule.check(&map);
         assert!(res.status == Status::NotMet);
 
+        // bar holds a Str, not a Bool, so neither side of the comparison can be Met
         rule = boolean("bar is false", "bar", false);
         res = rule.check(&map);
-        assert!(res.status == Status::Met);
+        assert!(res.status == Status::NotMet);
 
-        map.insert("quux".into(), "tRuE".into());
+        map.insert("quux".into(), "tRuE".parse::<Value>().unwrap());
         rule = boolean("quux is true", "quux", true);
         res = rule.check(&map);
         assert!(res.status == Status::Met);
 
     }
+
+    #[test]
+    fn int_ordered_rules() {
+        let map = get_test_data();
+        let mut rule = int_less_than("foo < 2", "foo", 2);
+        let mut res = rule.check(&map);
+        assert!(res.status == Status::Met);
+
+        rule = int_less_than("foo < 1", "foo", 1);
+        res = rule.check(&map);
+        assert!(res.status == Status::NotMet);
+
+        rule = int_greater_than("foo > 0", "foo", 0);
+        res = rule.check(&map);
+        assert!(res.status == Status::Met);
+
+        rule = int_greater_than("foo > 1", "foo", 1);
+        res = rule.check(&map);
+        assert!(res.status == Status::NotMet);
+
+        rule = int_less_or_equal("foo <= 1", "foo", 1);
+        res = rule.check(&map);
+        assert!(res.status == Status::Met);
+
+        rule = int_greater_or_equal("foo >= 1", "foo", 1);
+        res = rule.check(&map);
+        assert!(res.status == Status::Met);
+
+        // Values not convertible to int should be NotMet
+        rule = int_less_than("bar < 1", "bar", 1);
+        res = rule.check(&map);
+        assert!(res.status == Status::NotMet);
+    }
+
+    #[test]
+    fn string_ordered_rule() {
+        let map = get_test_data();
+        let mut rule = string_ordered("bar < 'baz'",
+                                      "bar",
+                                      OrderingOp::LessThan,
+                                      "baz",
+                                      Comparator::Lexicographic);
+        let mut res = rule.check(&map);
+        assert!(res.status == Status::Met);
+
+        rule = string_ordered("bar > 'baz'",
+                              "bar",
+                              OrderingOp::GreaterThan,
+                              "baz",
+                              Comparator::Lexicographic);
+        res = rule.check(&map);
+        assert!(res.status == Status::NotMet);
+
+        rule = string_ordered("bar >= 'BAR' case-insensitively",
+                              "bar",
+                              OrderingOp::GreaterOrEqual,
+                              "BAR",
+                              Comparator::CaseInsensitive);
+        res = rule.check(&map);
+        assert!(res.status == Status::Met);
+
+        // Values not convertible to Str should be NotMet
+        rule = string_ordered("foo < 'baz'",
+                              "foo",
+                              OrderingOp::LessThan,
+                              "baz",
+                              Comparator::Lexicographic);
+        res = rule.check(&map);
+        assert!(res.status == Status::NotMet);
+    }
+
+    #[test]
+    fn satisfying_facts_and() {
+        let tree = and(vec![string_equals("name is John Doe", "name", "John Doe"),
+                            int_range("thinking_of between 5 and 10", "thinking_of", 5, 10)]);
+        let facts = tree.satisfying_facts().unwrap();
+        assert!(tree.check(&facts).status == Status::Met);
+    }
+
+    #[test]
+    fn satisfying_facts_and_conflict() {
+        let tree = and(vec![int_equals("foo = 1", "foo", 1), int_equals("foo = 2", "foo", 2)]);
+        assert_eq!(tree.satisfying_facts(),
+                   Err(UnsatisfiableError::Conflict("foo".into())));
+    }
+
+    #[test]
+    fn satisfying_facts_or() {
+        let tree = or(vec![int_equals("foo = 1", "foo", 1), int_equals("foo = 2", "foo", 2)]);
+        let facts = tree.satisfying_facts().unwrap();
+        assert!(tree.check(&facts).status == Status::Met);
+    }
+
+    #[test]
+    fn satisfying_facts_n_of() {
+        let tree = n_of(2,
+                        vec![int_equals("foo = 1", "foo", 1),
+                             string_equals("bar = 'bar'", "bar", "bar"),
+                             int_equals("foo = 2", "foo", 2)]);
+        let facts = tree.satisfying_facts().unwrap();
+        assert!(tree.check(&facts).status == Status::Met);
+
+        // No size-2 subset of these children is mutually consistent
+        let unsat = n_of(2,
+                         vec![int_equals("foo = 1", "foo", 1),
+                              int_equals("foo = 2", "foo", 2),
+                              int_equals("foo = 3", "foo", 3)]);
+        assert!(unsat.satisfying_facts().is_err());
+    }
+
+    #[test]
+    fn failing_facts_leaf() {
+        let tree = and(vec![int_equals("foo = 1", "foo", 1),
+                            string_equals("bar = 'bar'", "bar", "bar")]);
+        let facts = tree.failing_facts().unwrap();
+        assert!(tree.check(&facts).status != Status::Met);
+    }
+
+    #[test]
+    fn satisfying_facts_string_ordered() {
+        let tree = string_ordered("bar < 'baz'",
+                                  "bar",
+                                  OrderingOp::LessThan,
+                                  "baz",
+                                  Comparator::Lexicographic);
+        let facts = tree.satisfying_facts().unwrap();
+        assert!(tree.check(&facts).status == Status::Met);
+
+        // No string sorts below "", so this can't be satisfied
+        let unsat = string_ordered("bar < ''",
+                                   "bar",
+                                   OrderingOp::LessThan,
+                                   "",
+                                   Comparator::Lexicographic);
+        assert!(unsat.satisfying_facts().is_err());
+    }
 }
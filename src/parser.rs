@@ -0,0 +1,511 @@
+//! A small recursive-descent parser for a text DSL that builds a [`Rule`][1] tree, so rule
+//! sets can live in config files instead of nested Rust constructor calls.
+//!
+//! ## Grammar
+//!
+//! ```text
+//! expr       := (STRING ':')? (combinator | leaf)
+//! combinator := ('and' | 'or') '(' expr (',' expr)* ')'
+//!             | 'n_of' '(' INT ',' expr (',' expr)* ')'
+//! leaf       := IDENT '==' (STRING | INT)
+//!             | IDENT 'in' INT '..' INT
+//!             | IDENT 'is' ('true' | 'false')
+//! ```
+//!
+//! A `STRING ':'` prefix supplies the description for the leaf that follows it; it has no
+//! effect on a combinator, since [`Rule::And`][2]/[`Rule::Or`][2]/[`Rule::NumberOf`][2] have no
+//! description of their own.
+//!
+//! [1]: ../enum.Rule.html
+//! [2]: ../enum.Rule.html
+
+use std::error::Error;
+use std::fmt;
+
+use {and, n_of, or, Constraint, Rule};
+
+// ***********************************************************************
+// TOKENS
+// **********************************************************************
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Eq,
+    Range,
+    LParen,
+    RParen,
+    Comma,
+    Colon,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(offset, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        match c {
+            '(' => {
+                chars.next();
+                tokens.push((Token::LParen, offset));
+            }
+            ')' => {
+                chars.next();
+                tokens.push((Token::RParen, offset));
+            }
+            ',' => {
+                chars.next();
+                tokens.push((Token::Comma, offset));
+            }
+            ':' => {
+                chars.next();
+                tokens.push((Token::Colon, offset));
+            }
+            '=' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '=')) => tokens.push((Token::Eq, offset)),
+                    _ => return Err(ParseError::UnexpectedToken(offset)),
+                }
+            }
+            '.' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '.')) => tokens.push((Token::Range, offset)),
+                    _ => return Err(ParseError::UnexpectedToken(offset)),
+                }
+            }
+            '"' => {
+                chars.next();
+                let start = offset + 1;
+                let mut end = None;
+                while let Some(&(o, ch)) = chars.peek() {
+                    chars.next();
+                    if ch == '"' {
+                        end = Some(o);
+                        break;
+                    }
+                }
+                match end {
+                    Some(end) => tokens.push((Token::Str(input[start..end].to_owned()), offset)),
+                    None => return Err(ParseError::Expected("closing '\"'".into(), offset)),
+                }
+            }
+            '-' | '0'..='9' => {
+                let mut end = offset + c.len_utf8();
+                chars.next();
+                while let Some(&(o, ch)) = chars.peek() {
+                    if ch.is_ascii_digit() {
+                        end = o + ch.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match input[offset..end].parse::<i64>() {
+                    Ok(n) => tokens.push((Token::Int(n), offset)),
+                    Err(_) => return Err(ParseError::UnexpectedToken(offset)),
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = offset + c.len_utf8();
+                chars.next();
+                while let Some(&(o, ch)) = chars.peek() {
+                    if ch.is_alphanumeric() || ch == '_' {
+                        end = o + ch.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((Token::Ident(input[offset..end].to_owned()), offset));
+            }
+            _ => return Err(ParseError::UnexpectedToken(offset)),
+        }
+    }
+    Ok(tokens)
+}
+
+// ***********************************************************************
+// PARSER
+// **********************************************************************
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    input: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<(Token, usize)>, input: &'a str) -> Parser<'a> {
+        Parser {
+            tokens: tokens,
+            pos: 0,
+            input: input,
+        }
+    }
+
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next_token(&mut self) -> Option<(Token, usize)> {
+        let tok = self.tokens.get(self.pos).cloned();
+        if tok.is_some() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|&(_, offset)| offset)
+            .unwrap_or_else(|| self.input.len())
+    }
+
+    fn expect(&mut self, expected: &Token, what: &str) -> Result<(), ParseError> {
+        match self.next_token() {
+            Some((ref tok, _)) if tok == expected => Ok(()),
+            Some((_, offset)) => Err(ParseError::Expected(what.into(), offset)),
+            None => Err(ParseError::Expected(what.into(), self.input.len())),
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<i64, ParseError> {
+        match self.next_token() {
+            Some((Token::Int(n), _)) => Ok(n),
+            Some((_, offset)) => Err(ParseError::Expected("integer".into(), offset)),
+            None => Err(ParseError::Expected("integer".into(), self.input.len())),
+        }
+    }
+
+    /// Parses an optional `"desc":` prefix.
+    fn parse_desc_prefix(&mut self) -> Option<String> {
+        let is_desc_prefix = match self.peek() {
+            Some(&(Token::Str(_), _)) => {
+                match self.tokens.get(self.pos + 1) {
+                    Some(&(Token::Colon, _)) => true,
+                    _ => false,
+                }
+            }
+            _ => false,
+        };
+        if !is_desc_prefix {
+            return None;
+        }
+        let desc = match self.next_token() {
+            Some((Token::Str(s), _)) => s,
+            _ => unreachable!(),
+        };
+        self.next_token(); // the ':'
+        Some(desc)
+    }
+
+    fn parse_expr(&mut self) -> Result<Rule, ParseError> {
+        let desc = self.parse_desc_prefix();
+        let rule = match self.peek().cloned() {
+            Some((Token::Ident(ref ident), offset)) if ident == "and" => {
+                self.next_token();
+                and(self.parse_paren_list(offset)?)
+            }
+            Some((Token::Ident(ref ident), offset)) if ident == "or" => {
+                self.next_token();
+                or(self.parse_paren_list(offset)?)
+            }
+            Some((Token::Ident(ref ident), offset)) if ident == "n_of" => {
+                self.next_token();
+                self.parse_n_of(offset)?
+            }
+            Some((Token::Ident(ref field), _)) => {
+                let field = field.clone();
+                self.next_token();
+                self.parse_leaf(field)?
+            }
+            Some((_, offset)) => return Err(ParseError::UnexpectedToken(offset)),
+            None => return Err(ParseError::Expected("expression".into(), self.input.len())),
+        };
+        Ok(match desc {
+            // `And`/`Or`/`NumberOf` have no description field of their own, so a
+            // `"desc":` prefix in front of one is accepted but has no effect.
+            Some(desc) => {
+                if let Rule::Rule(_, field, constraint) = rule {
+                    Rule::Rule(desc, field, constraint)
+                } else {
+                    rule
+                }
+            }
+            None => rule,
+        })
+    }
+
+    /// Parses `'(' expr (',' expr)* ')'`, used by `and`/`or`.
+    fn parse_paren_list(&mut self, start_offset: usize) -> Result<Vec<Rule>, ParseError> {
+        self.expect(&Token::LParen, "'('")?;
+        if let Some(&(Token::RParen, _)) = self.peek() {
+            self.next_token();
+            return Err(ParseError::EmptyGroup(start_offset));
+        }
+        let mut children = Vec::new();
+        loop {
+            children.push(self.parse_expr()?);
+            match self.next_token() {
+                Some((Token::Comma, _)) => continue,
+                Some((Token::RParen, _)) => break,
+                Some((_, offset)) => return Err(ParseError::Expected("',' or ')'".into(), offset)),
+                None => return Err(ParseError::Expected("',' or ')'".into(), self.input.len())),
+            }
+        }
+        Ok(children)
+    }
+
+    /// Parses `'(' INT ',' expr (',' expr)* ')'`, used by `n_of`.
+    fn parse_n_of(&mut self, start_offset: usize) -> Result<Rule, ParseError> {
+        self.expect(&Token::LParen, "'('")?;
+        let count_offset = self.offset();
+        let count = self.expect_int()?;
+        self.expect(&Token::Comma, "','")?;
+        if let Some(&(Token::RParen, _)) = self.peek() {
+            self.next_token();
+            return Err(ParseError::EmptyGroup(start_offset));
+        }
+        let mut children = Vec::new();
+        loop {
+            children.push(self.parse_expr()?);
+            match self.next_token() {
+                Some((Token::Comma, _)) => continue,
+                Some((Token::RParen, _)) => break,
+                Some((_, offset)) => return Err(ParseError::Expected("',' or ')'".into(), offset)),
+                None => return Err(ParseError::Expected("',' or ')'".into(), self.input.len())),
+            }
+        }
+        if count < 0 || count as usize > children.len() {
+            return Err(ParseError::Expected(format!("a count between 0 and {}", children.len()),
+                                             count_offset));
+        }
+        Ok(n_of(count as usize, children))
+    }
+
+    /// Parses `IDENT '==' (STRING | INT)`, `IDENT 'in' INT '..' INT`, or `IDENT 'is' BOOL`,
+    /// given that `IDENT` has already been consumed as `field`.
+    fn parse_leaf(&mut self, field: String) -> Result<Rule, ParseError> {
+        match self.next_token() {
+            Some((Token::Eq, _)) => {
+                match self.next_token() {
+                    Some((Token::Str(s), _)) => {
+                        let desc = format!("{} == \"{}\"", field, s);
+                        Ok(Rule::Rule(desc, field, Constraint::StringEquals(s)))
+                    }
+                    Some((Token::Int(i), offset)) => {
+                        if i < i32::min_value() as i64 || i > i32::max_value() as i64 {
+                            return Err(ParseError::Expected("32-bit integer".into(), offset));
+                        }
+                        let desc = format!("{} == {}", field, i);
+                        Ok(Rule::Rule(desc, field, Constraint::IntEquals(i as i32)))
+                    }
+                    Some((_, offset)) => {
+                        Err(ParseError::Expected("string or integer literal".into(), offset))
+                    }
+                    None => {
+                        Err(ParseError::Expected("string or integer literal".into(),
+                                                  self.input.len()))
+                    }
+                }
+            }
+            Some((Token::Ident(ref kw), _)) if kw == "in" => {
+                let start_offset = self.offset();
+                let start = self.expect_int()?;
+                self.expect(&Token::Range, "'..'")?;
+                let end_offset = self.offset();
+                let end = self.expect_int()?;
+                for &(n, offset) in &[(start, start_offset), (end, end_offset)] {
+                    if n < i32::min_value() as i64 || n > i32::max_value() as i64 {
+                        return Err(ParseError::Expected("32-bit integer".into(), offset));
+                    }
+                }
+                let desc = format!("{} in {}..{}", field, start, end);
+                Ok(Rule::Rule(desc, field, Constraint::IntRange(start as i32, end as i32)))
+            }
+            Some((Token::Ident(ref kw), _)) if kw == "is" => {
+                match self.next_token() {
+                    Some((Token::Ident(ref b), _)) if b == "true" => {
+                        let desc = format!("{} is true", field);
+                        Ok(Rule::Rule(desc, field, Constraint::Boolean(true)))
+                    }
+                    Some((Token::Ident(ref b), _)) if b == "false" => {
+                        let desc = format!("{} is false", field);
+                        Ok(Rule::Rule(desc, field, Constraint::Boolean(false)))
+                    }
+                    Some((_, offset)) => {
+                        Err(ParseError::Expected("'true' or 'false'".into(), offset))
+                    }
+                    None => Err(ParseError::Expected("'true' or 'false'".into(), self.input.len())),
+                }
+            }
+            Some((_, offset)) => Err(ParseError::Expected("'==', 'in', or 'is'".into(), offset)),
+            None => Err(ParseError::Expected("'==', 'in', or 'is'".into(), self.input.len())),
+        }
+    }
+}
+
+impl Rule {
+    /// Parses `input` as a rule expression, producing the same tree that the equivalent
+    /// `and`/`or`/`n_of`/`*_equals`/`int_range`/`boolean` constructor calls would.
+    ///
+    /// ```rust
+    /// # extern crate ruuls;
+    /// let tree = ruuls::Rule::parse(r#"
+    ///     and(
+    ///         "Name is John Doe": name == "John Doe",
+    ///         or(
+    ///             "Favorite number is 5": fav_number == 5,
+    ///             "Thinking of a number between 5 and 10": thinking_of in 5..10
+    ///         )
+    ///     )
+    /// "#).unwrap();
+    /// ```
+    pub fn parse(input: &str) -> Result<Rule, ParseError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser::new(tokens, input);
+        let rule = parser.parse_expr()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(ParseError::UnexpectedToken(parser.offset()));
+        }
+        Ok(rule)
+    }
+}
+
+// ***********************************************************************
+// PARSE ERROR
+// **********************************************************************
+/// Why a DSL string failed to parse into a `Rule`, with the byte offset it happened at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A token was found where none of the expected productions apply
+    UnexpectedToken(usize),
+    /// A specific token or construct was expected but not found
+    Expected(String, usize),
+    /// A combinator (`and`/`or`/`n_of`) had no children
+    EmptyGroup(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnexpectedToken(offset) => {
+                write!(f, "unexpected token at offset {}", offset)
+            }
+            ParseError::Expected(ref what, offset) => {
+                write!(f, "expected {} at offset {}", what, offset)
+            }
+            ParseError::EmptyGroup(offset) => {
+                write!(f, "combinator with no children at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        "failed to parse rule expression"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {string_equals, int_equals, int_range, Status};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn parses_readme_example() {
+        let parsed = Rule::parse(r#"
+            and(
+                "Name is John Doe": name == "John Doe",
+                or(
+                    "Favorite number is 5": fav_number == 5,
+                    "Thinking of a number between 5 and 10": thinking_of in 5..10
+                )
+            )
+        "#)
+            .unwrap();
+
+        let hand_built = and(vec![string_equals("Name is John Doe", "name", "John Doe"),
+                                  or(vec![int_equals("Favorite number is 5", "fav_number", 5),
+                                         int_range("Thinking of a number between 5 and 10",
+                                                    "thinking_of",
+                                                    5,
+                                                    10)])]);
+
+        let mut facts = BTreeMap::new();
+        facts.insert("name".into(), "John Doe".into());
+        facts.insert("fav_number".into(), 5.into());
+
+        assert_eq!(parsed.check(&facts).status, Status::Met);
+        assert_eq!(parsed.check(&facts).status, hand_built.check(&facts).status);
+    }
+
+    #[test]
+    fn parses_boolean_leaf() {
+        let rule = Rule::parse("flag is true").unwrap();
+        let mut facts = BTreeMap::new();
+        facts.insert("flag".into(), true.into());
+        assert_eq!(rule.check(&facts).status, Status::Met);
+    }
+
+    #[test]
+    fn errors_on_empty_group() {
+        match Rule::parse("and()") {
+            Err(ParseError::EmptyGroup(_)) => {}
+            other => panic!("expected EmptyGroup, got {:?}", other),
+        }
+
+        match Rule::parse("or()") {
+            Err(ParseError::EmptyGroup(_)) => {}
+            other => panic!("expected EmptyGroup, got {:?}", other),
+        }
+
+        match Rule::parse("n_of(2,)") {
+            Err(ParseError::EmptyGroup(_)) => {}
+            other => panic!("expected EmptyGroup, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_on_n_of_count_out_of_range() {
+        // count greater than the number of children
+        match Rule::parse("n_of(5, foo == 1)") {
+            Err(ParseError::Expected(_, _)) => {}
+            other => panic!("expected Expected, got {:?}", other),
+        }
+
+        // negative count
+        match Rule::parse("n_of(-1, foo == 1, bar == 2)") {
+            Err(ParseError::Expected(_, _)) => {}
+            other => panic!("expected Expected, got {:?}", other),
+        }
+
+        // 0 and children.len() are both valid counts
+        assert!(Rule::parse("n_of(0, foo == 1)").is_ok());
+        assert!(Rule::parse("n_of(1, foo == 1)").is_ok());
+    }
+
+    #[test]
+    fn errors_on_unexpected_token() {
+        match Rule::parse("and(,)") {
+            Err(ParseError::UnexpectedToken(_)) => {}
+            other => panic!("expected UnexpectedToken, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_on_missing_operator() {
+        match Rule::parse("name") {
+            Err(ParseError::Expected(_, _)) => {}
+            other => panic!("expected Expected, got {:?}", other),
+        }
+    }
+}
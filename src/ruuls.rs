@@ -1,5 +1,7 @@
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::ops::{BitOr, BitAnd};
+use std::str::FromStr;
 
 // ***********************************************************************
 // STATUS
@@ -38,6 +40,91 @@ impl BitOr for Status {
     }
 }
 
+// ***********************************************************************
+// VALUE
+// **********************************************************************
+/// A typed fact value.
+///
+/// Facts are decided to be one of these types once, at insertion into the
+/// facts map, instead of being re-parsed as a string on every check.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    /// Returns this value as an `i64` if it holds a numeric value, `None` otherwise.
+    ///
+    /// A `Float` is only numeric here if it has no fractional component.
+    fn as_int(&self) -> Option<i64> {
+        match *self {
+            Value::Int(i) => Some(i),
+            Value::Float(f) if f.fract() == 0.0 => Some(f as i64),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::Str(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Str(s)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Value {
+        Value::Int(i as i64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value {
+        Value::Int(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Value {
+        Value::Float(f)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl FromStr for Value {
+    type Err = ();
+
+    /// Infers the narrowest type that `s` fits: `bool`, then `i64`, then `f64`,
+    /// falling back to `Str` if none of those parse. Never fails.
+    fn from_str(s: &str) -> Result<Value, ()> {
+        match s.to_lowercase().as_ref() {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            _ => {}
+        }
+        if let Ok(i) = s.parse::<i64>() {
+            return Ok(Value::Int(i));
+        }
+        if let Ok(f) = s.parse::<f64>() {
+            return Ok(Value::Float(f));
+        }
+        Ok(Value::Str(s.to_owned()))
+    }
+}
+
 // ***********************************************************************
 // Rule
 // **********************************************************************
@@ -59,7 +146,7 @@ pub enum Rule {
 impl Rule {
     /// Starting at this node, recursively check (depth-first) any child nodes and 
     /// aggregate the results
-    pub fn check(&self, info: &BTreeMap<String, String>) -> RuleResult {
+    pub fn check(&self, info: &BTreeMap<String, Value>) -> RuleResult {
         match *self {
             Rule::And(ref rules) => {
                 let mut status = Status::Met;
@@ -98,9 +185,12 @@ impl Rule {
                                         }
                                     })
                                     .collect::<Vec<_>>();
+                // A `count` greater than the number of children can never be Met; clamp it so
+                // the NotMet threshold below can't underflow for an out-of-range count.
+                let threshold = count.min(children.len());
                 let status = if met_count >= count {
                     Status::Met
-                } else if failed_count >= children.len() - count + 1 {
+                } else if failed_count >= children.len() - threshold + 1 {
                     Status::NotMet
                 } else {
                     Status::Unknown
@@ -127,6 +217,238 @@ impl Rule {
             }
         }
     }
+
+    /// Generates a fact map which makes `self.check()` return `Met`.
+    ///
+    /// `And` merges the facts generated by each child, failing if two children assign
+    /// conflicting values to the same field. `Or` takes the first child that generates
+    /// successfully. `NumberOf(n, _)` picks the first size-`n` subset of children whose
+    /// generated facts are mutually consistent, backtracking to later subsets on conflict.
+    pub fn satisfying_facts(&self) -> Result<BTreeMap<String, Value>, UnsatisfiableError> {
+        match *self {
+            Rule::And(ref rules) => {
+                let mut map = BTreeMap::new();
+                for rule in rules {
+                    merge_facts(&mut map, rule.satisfying_facts()?)?;
+                }
+                Ok(map)
+            }
+            Rule::Or(ref rules) => {
+                for rule in rules {
+                    if let Ok(map) = rule.satisfying_facts() {
+                        return Ok(map);
+                    }
+                }
+                Err(UnsatisfiableError::NoSatisfyingSubset)
+            }
+            Rule::NumberOf(count, ref rules) => satisfying_subset(count, rules),
+            Rule::Rule(_, ref field, ref constraint) => {
+                let mut map = BTreeMap::new();
+                map.insert(field.clone(), constraint.satisfying_value()?);
+                Ok(map)
+            }
+        }
+    }
+
+    /// Generates a fact map which makes one reachable leaf of the tree `NotMet`.
+    ///
+    /// Starts from a satisfying fact map (falling back to an empty one if the tree can't be
+    /// satisfied at all) and overwrites the field of the first leaf found with a value that
+    /// violates that leaf's constraint.
+    pub fn failing_facts(&self) -> Result<BTreeMap<String, Value>, UnsatisfiableError> {
+        let mut map = self.satisfying_facts().unwrap_or_else(|_| BTreeMap::new());
+        match self.first_leaf() {
+            Some((field, constraint)) => {
+                map.insert(field, constraint.failing_value()?);
+                Ok(map)
+            }
+            None => Err(UnsatisfiableError::NoSatisfyingSubset),
+        }
+    }
+
+    fn first_leaf(&self) -> Option<(String, &Constraint)> {
+        match *self {
+            Rule::Rule(_, ref field, ref constraint) => Some((field.clone(), constraint)),
+            Rule::And(ref rules) |
+            Rule::Or(ref rules) => rules.iter().filter_map(|r| r.first_leaf()).next(),
+            Rule::NumberOf(_, ref rules) => rules.iter().filter_map(|r| r.first_leaf()).next(),
+        }
+    }
+}
+
+/// Merges `other` into `map`, erroring if a field is assigned conflicting values.
+fn merge_facts(map: &mut BTreeMap<String, Value>,
+               other: BTreeMap<String, Value>)
+               -> Result<(), UnsatisfiableError> {
+    for (field, value) in other {
+        match map.get(&field) {
+            Some(existing) if *existing != value => return Err(UnsatisfiableError::Conflict(field)),
+            _ => {}
+        }
+        map.insert(field, value);
+    }
+    Ok(())
+}
+
+/// Picks the first size-`n` combination (in index order) of `rules` whose satisfying facts
+/// can be merged without conflict, backtracking to later combinations otherwise.
+fn satisfying_subset(n: usize, rules: &[Rule]) -> Result<BTreeMap<String, Value>, UnsatisfiableError> {
+    if n > rules.len() {
+        return Err(UnsatisfiableError::NoSatisfyingSubset);
+    }
+    let children: Vec<_> = rules.iter().map(|r| r.satisfying_facts()).collect();
+    for combo in combinations(rules.len(), n) {
+        let mut map = BTreeMap::new();
+        let mut ok = true;
+        for i in combo {
+            match children[i] {
+                Ok(ref child_map) => {
+                    if merge_facts(&mut map, child_map.clone()).is_err() {
+                        ok = false;
+                        break;
+                    }
+                }
+                Err(_) => {
+                    ok = false;
+                    break;
+                }
+            }
+        }
+        if ok {
+            return Ok(map);
+        }
+    }
+    Err(UnsatisfiableError::NoSatisfyingSubset)
+}
+
+/// All size-`n` subsets of `0..len`, in lexicographic index order.
+fn combinations(len: usize, n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    if n > len {
+        return Vec::new();
+    }
+    let mut result = Vec::new();
+    for i in 0..len {
+        if len - i < n {
+            break;
+        }
+        for rest in combinations(len - i - 1, n - 1) {
+            let mut combo = vec![i];
+            combo.extend(rest.into_iter().map(|r| r + i + 1));
+            result.push(combo);
+        }
+    }
+    result
+}
+
+// ***********************************************************************
+// UNSATISFIABLE ERROR
+// **********************************************************************
+/// Why a tree could not be inverted into a fact map by [`Rule::satisfying_facts`][1]
+/// or [`Rule::failing_facts`][2]
+///
+/// [1]: enum.Rule.html#method.satisfying_facts
+/// [2]: enum.Rule.html#method.failing_facts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnsatisfiableError {
+    /// Two leaves assigned conflicting values to the same field
+    Conflict(String),
+    /// No combination of children could be generated without conflict
+    NoSatisfyingSubset,
+    /// No string value exists that would satisfy (or violate) a `StringOrdered`
+    /// constraint with this bound, e.g. nothing under `Ord` sorts below `""`
+    NoSatisfyingValue(String),
+}
+
+impl ::std::fmt::Display for UnsatisfiableError {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            UnsatisfiableError::Conflict(ref field) => {
+                write!(f, "conflicting values assigned to field '{}'", field)
+            }
+            UnsatisfiableError::NoSatisfyingSubset => {
+                write!(f, "no consistent combination of children could be found")
+            }
+            UnsatisfiableError::NoSatisfyingValue(ref bound) => {
+                write!(f, "no value exists that satisfies the ordering against '{}'", bound)
+            }
+        }
+    }
+}
+
+impl ::std::error::Error for UnsatisfiableError {
+    fn description(&self) -> &str {
+        match *self {
+            UnsatisfiableError::Conflict(_) => "conflicting values assigned to the same field",
+            UnsatisfiableError::NoSatisfyingSubset => {
+                "no consistent combination of children could be found"
+            }
+            UnsatisfiableError::NoSatisfyingValue(_) => {
+                "no value exists that satisfies the ordering constraint"
+            }
+        }
+    }
+}
+
+// ***********************************************************************
+// ORDERING
+// **********************************************************************
+/// The relation an ordered constraint checks for, relative to its bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderingOp {
+    LessThan,
+    GreaterThan,
+    LessOrEqual,
+    GreaterOrEqual,
+}
+
+impl OrderingOp {
+    fn is_met_by(&self, ord: Ordering) -> bool {
+        match *self {
+            OrderingOp::LessThan => ord == Ordering::Less,
+            OrderingOp::GreaterThan => ord == Ordering::Greater,
+            OrderingOp::LessOrEqual => ord != Ordering::Greater,
+            OrderingOp::GreaterOrEqual => ord != Ordering::Less,
+        }
+    }
+
+    fn negated(&self) -> OrderingOp {
+        match *self {
+            OrderingOp::LessThan => OrderingOp::GreaterOrEqual,
+            OrderingOp::GreaterThan => OrderingOp::LessOrEqual,
+            OrderingOp::LessOrEqual => OrderingOp::GreaterThan,
+            OrderingOp::GreaterOrEqual => OrderingOp::LessThan,
+        }
+    }
+}
+
+/// How two strings should be ordered against one another.
+///
+/// Plugging this in as a parameter of the rule (rather than hardcoding `Ord`)
+/// lets the same tree order one field case-insensitively and another numerically,
+/// without having to pre-normalize the facts going in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    Lexicographic,
+    CaseInsensitive,
+    Numeric,
+}
+
+impl Comparator {
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        match *self {
+            Comparator::Lexicographic => a.cmp(b),
+            Comparator::CaseInsensitive => a.to_lowercase().cmp(&b.to_lowercase()),
+            Comparator::Numeric => {
+                match (a.parse::<f64>(), b.parse::<f64>()) {
+                    (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                    _ => a.cmp(b),
+                }
+            }
+        }
+    }
 }
 
 // ***********************************************************************
@@ -137,23 +459,35 @@ pub enum Constraint {
     StringEquals(String),
     IntEquals(i32),
     IntRange(i32, i32),
+    IntLessThan(i32),
+    IntGreaterThan(i32),
+    IntLessOrEqual(i32),
+    IntGreaterOrEqual(i32),
+    StringOrdered {
+        op: OrderingOp,
+        bound: String,
+        comparator: Comparator,
+    },
     Boolean(bool),
 }
 
 impl Constraint {
-    pub fn check(&self, val: &str) -> Status {
+    pub fn check(&self, val: &Value) -> Status {
         match *self {
             Constraint::StringEquals(ref s) => {
-                if val == s {
-                    Status::Met
+                if let Value::Str(ref val) = *val {
+                    if val == s {
+                        Status::Met
+                    } else {
+                        Status::NotMet
+                    }
                 } else {
                     Status::NotMet
                 }
             }
             Constraint::IntEquals(i) => {
-                let parse_res = val.parse::<i32>();
-                if let Ok(val) = parse_res {
-                    if val == i {
+                if let Some(val) = val.as_int() {
+                    if val == i as i64 {
                         Status::Met
                     } else {
                         Status::NotMet
@@ -163,9 +497,25 @@ impl Constraint {
                 }
             }
             Constraint::IntRange(start, end) => {
-                let parse_res = val.parse::<i32>();
-                if let Ok(val) = parse_res {
-                    if start <= val && val <= end {
+                if let Some(val) = val.as_int() {
+                    if start as i64 <= val && val <= end as i64 {
+                        Status::Met
+                    } else {
+                        Status::NotMet
+                    }
+                } else {
+                    Status::NotMet
+                }
+            }
+            Constraint::IntLessThan(i) => check_ordered_int(val, OrderingOp::LessThan, i),
+            Constraint::IntGreaterThan(i) => check_ordered_int(val, OrderingOp::GreaterThan, i),
+            Constraint::IntLessOrEqual(i) => check_ordered_int(val, OrderingOp::LessOrEqual, i),
+            Constraint::IntGreaterOrEqual(i) => {
+                check_ordered_int(val, OrderingOp::GreaterOrEqual, i)
+            }
+            Constraint::StringOrdered { op, ref bound, comparator } => {
+                if let Value::Str(ref val) = *val {
+                    if op.is_met_by(comparator.compare(val, bound)) {
                         Status::Met
                     } else {
                         Status::NotMet
@@ -175,9 +525,12 @@ impl Constraint {
                 }
             }
             Constraint::Boolean(b) => {
-                let bool_val = &val.to_lowercase() == "true";
-                if bool_val == b {
-                    Status::Met
+                if let Value::Bool(val) = *val {
+                    if val == b {
+                        Status::Met
+                    } else {
+                        Status::NotMet
+                    }
                 } else {
                     Status::NotMet
                 }
@@ -186,6 +539,88 @@ impl Constraint {
     }
 }
 
+fn check_ordered_int(val: &Value, op: OrderingOp, bound: i32) -> Status {
+    if let Some(val) = val.as_int() {
+        if op.is_met_by(val.cmp(&(bound as i64))) {
+            Status::Met
+        } else {
+            Status::NotMet
+        }
+    } else {
+        Status::NotMet
+    }
+}
+
+impl Constraint {
+    /// A `Value` for which `self.check()` returns `Met`
+    fn satisfying_value(&self) -> Result<Value, UnsatisfiableError> {
+        match *self {
+            Constraint::StringEquals(ref s) => Ok(Value::Str(s.clone())),
+            Constraint::IntEquals(i) => Ok(Value::Int(i as i64)),
+            Constraint::IntRange(start, _) => Ok(Value::Int(start as i64)),
+            Constraint::IntLessThan(i) => Ok(Value::Int(i as i64 - 1)),
+            Constraint::IntGreaterThan(i) => Ok(Value::Int(i as i64 + 1)),
+            Constraint::IntLessOrEqual(i) => Ok(Value::Int(i as i64)),
+            Constraint::IntGreaterOrEqual(i) => Ok(Value::Int(i as i64)),
+            Constraint::StringOrdered { op, ref bound, comparator } => {
+                satisfying_string(op, bound, comparator).map(Value::Str)
+            }
+            Constraint::Boolean(b) => Ok(Value::Bool(b)),
+        }
+    }
+
+    /// A `Value` for which `self.check()` returns `NotMet`
+    fn failing_value(&self) -> Result<Value, UnsatisfiableError> {
+        match *self {
+            Constraint::StringEquals(ref s) => Ok(Value::Str(format!("not-{}", s))),
+            Constraint::IntEquals(i) => Ok(Value::Int(i as i64 + 1)),
+            Constraint::IntRange(_, end) => Ok(Value::Int(end as i64 + 1)),
+            Constraint::IntLessThan(i) => Ok(Value::Int(i as i64)),
+            Constraint::IntGreaterThan(i) => Ok(Value::Int(i as i64)),
+            Constraint::IntLessOrEqual(i) => Ok(Value::Int(i as i64 + 1)),
+            Constraint::IntGreaterOrEqual(i) => Ok(Value::Int(i as i64 - 1)),
+            Constraint::StringOrdered { op, ref bound, comparator } => {
+                satisfying_string(op.negated(), bound, comparator).map(Value::Str)
+            }
+            Constraint::Boolean(b) => Ok(Value::Bool(!b)),
+        }
+    }
+}
+
+/// A string satisfying `op`/`comparator` against `bound`, or `Err` if no such string exists
+/// (there is no string below `""` under `Ord`, so `LessThan("")` is unsatisfiable, as is a
+/// negated `GreaterOrEqual("")` when computing a failing value).
+fn satisfying_string(op: OrderingOp,
+                      bound: &str,
+                      comparator: Comparator)
+                      -> Result<String, UnsatisfiableError> {
+    if let Comparator::Numeric = comparator {
+        if let Ok(b) = bound.parse::<f64>() {
+            let candidate = match op {
+                OrderingOp::LessThan => (b - 1.0).to_string(),
+                OrderingOp::GreaterThan => (b + 1.0).to_string(),
+                OrderingOp::LessOrEqual | OrderingOp::GreaterOrEqual => b.to_string(),
+            };
+            // NaN, infinite or very large bounds can defeat the +/- 1 above; the string
+            // candidates below are then compared as text and still satisfy `op`.
+            if op.is_met_by(comparator.compare(&candidate, bound)) {
+                return Ok(candidate);
+            }
+        }
+    }
+    match op {
+        OrderingOp::LessThan => {
+            if bound.is_empty() {
+                Err(UnsatisfiableError::NoSatisfyingValue(bound.to_owned()))
+            } else {
+                Ok(String::new())
+            }
+        }
+        OrderingOp::GreaterThan => Ok(format!("{}-", bound)),
+        OrderingOp::LessOrEqual | OrderingOp::GreaterOrEqual => Ok(bound.to_owned()),
+    }
+}
+
 // ***********************************************************************
 // Rule RESULT
 // **********************************************************************